@@ -0,0 +1,99 @@
+// Copyright (c) 2022, Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::fmt;
+
+use crate::nizk::DdhTupleNizk;
+use fastcrypto::groups::GroupElement;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroize;
+
+/// The length in bytes of the AES-256 keys derived by the schemes in `ecies_v0`.
+pub const AES_KEY_LENGTH: usize = 32;
+
+/// A secret key for the ECIES scheme implemented in `ecies_v0`.
+///
+/// Unlike most types in this crate, `PrivateKey` does not derive `Debug`, `PartialOrd`, `Ord` or
+/// `Hash`: printing, ordering or hashing a secret key can leak it, e.g. through log output, the
+/// order of entries in a sorted collection, or a `HashMap`'s bucket placement. `PartialEq`/`Eq`
+/// are instead implemented by hand to compare in constant time, so comparing two private keys
+/// cannot be used as a timing side channel, and the inner scalar is zeroized on drop.
+pub struct PrivateKey<G: GroupElement>(pub(crate) G::ScalarType);
+
+impl<G: GroupElement> fmt::Debug for PrivateKey<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PrivateKey").field(&"<redacted>").finish()
+    }
+}
+
+impl<G: GroupElement> Clone for PrivateKey<G> {
+    fn clone(&self) -> Self {
+        Self(self.0)
+    }
+}
+
+impl<G: GroupElement> PartialEq for PrivateKey<G>
+where
+    G::ScalarType: Serialize,
+{
+    fn eq(&self, other: &Self) -> bool {
+        let a = bcs::to_bytes(&self.0).expect("serialize should never fail");
+        let b = bcs::to_bytes(&other.0).expect("serialize should never fail");
+        a.ct_eq(&b).into()
+    }
+}
+
+impl<G: GroupElement> Eq for PrivateKey<G> where G::ScalarType: Serialize {}
+
+impl<G: GroupElement> Zeroize for PrivateKey<G>
+where
+    G::ScalarType: Zeroize,
+{
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<G: GroupElement> Drop for PrivateKey<G>
+where
+    G::ScalarType: Zeroize,
+{
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+// Serde's derive macro would otherwise infer bounds from `G` rather than `G::ScalarType`, so the
+// (de)serialization is spelled out explicitly rather than derived.
+impl<G: GroupElement> Serialize for PrivateKey<G>
+where
+    G::ScalarType: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, G: GroupElement> Deserialize<'de> for PrivateKey<G>
+where
+    G::ScalarType: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self(G::ScalarType::deserialize(deserializer)?))
+    }
+}
+
+/// A public key for the ECIES scheme implemented in `ecies_v0`. Unlike `PrivateKey`, there is
+/// nothing sensitive about a public key, so ordinary derives are fine here.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicKey<G: GroupElement>(pub(crate) G);
+
+/// Lets a custodian who was given an ephemeral decryption point recover the plaintext of a
+/// ciphertext without holding the matching secret key, with a NIZK proving the point was derived
+/// honestly from the ciphertext's ephemeral key and the custodian's own public key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecoveryPackage<G: GroupElement> {
+    pub(crate) ephemeral_key: G,
+    pub(crate) proof: DdhTupleNizk<G>,
+}
@@ -4,14 +4,16 @@
 use crate::ecies::{PrivateKey, PublicKey, RecoveryPackage, AES_KEY_LENGTH};
 use crate::nizk::{DLNizk, DdhTupleNizk};
 use crate::random_oracle::RandomOracle;
-use fastcrypto::aes::{Aes256Ctr, AesKey, Cipher, InitializationVector};
+use fastcrypto::aes::{
+    Aes256Ctr, Aes256Gcm, AesKey, AuthenticatedCipher, Cipher, InitializationVector,
+};
 use fastcrypto::error::{FastCryptoError, FastCryptoResult};
 use fastcrypto::groups::{FiatShamirChallenge, GroupElement, Scalar};
 use fastcrypto::hmac::{hkdf_sha3_256, HkdfIkm};
 use fastcrypto::traits::{AllowedRng, ToFromBytes};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use typenum::consts::{U16, U32};
+use typenum::consts::{U12, U16, U32};
 use zeroize::Zeroize;
 
 ///
@@ -24,19 +26,41 @@ use zeroize::Zeroize;
 /// APIs that use a random oracle must receive one as an argument. That RO must be unique and thus
 /// the caller should initialize/derive it using a unique prefix.
 ///
-/// The encryption uses AES Counter mode and is not CCA secure as is.
+/// The encryption uses AES Counter mode and is not CCA secure as is. `AuthenticatedEncryption<G>`
+/// below is the same construction but with AES-256-GCM instead, which does give IND-CCA2 security
+/// and should be preferred whenever ciphertexts may come from an untrusted sender.
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Encryption<G: GroupElement> {
     ephemeral_key: G,
     data: Vec<u8>,
     hkdf_info: usize,
+    /// Associated data bound into the key derivation, e.g. a recipient identity or session id in
+    /// `MultiRecipientEncryption`. Empty for a plain single-recipient `Encryption`.
+    ad: Vec<u8>,
+}
+
+/// Authenticated ECIES encryption using a generic group and AES-256-GCM.
+///
+/// This is the same construction as `Encryption<G>` but the symmetric step is an AEAD instead of
+/// a plain stream cipher: the key is derived with the same `hkdf`, the GCM nonce can stay zero
+/// since the ephemeral key `rG` is fresh per message, and the serialized `ephemeral_key` is fed in
+/// as associated data so a ciphertext cannot be replayed under a different ephemeral key. The
+/// resulting tag makes forged or truncated ciphertexts fail to decrypt instead of silently
+/// producing garbage plaintext, so this mode is safe to use against untrusted senders.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthenticatedEncryption<G: GroupElement> {
+    ephemeral_key: G,
+    data: Vec<u8>,
+    hkdf_info: usize,
 }
 
 /// Multi-recipient encryption with a proof-of-knowledge of the plaintexts (when the encryption is
-/// valid).
+/// valid). Each recipient's slot additionally carries caller-supplied associated data (e.g. a
+/// recipient identity, a session/epoch id, a DKG round number) which is bound into that slot's
+/// key derivation and into the NIZK, so a ciphertext cannot be re-targeted to a different slot.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct MultiRecipientEncryption<G: GroupElement>(G, Vec<Vec<u8>>, DLNizk<G>);
+pub struct MultiRecipientEncryption<G: GroupElement>(G, Vec<Vec<u8>>, DLNizk<G>, Vec<Vec<u8>>);
 
 impl<G> PrivateKey<G>
 where
@@ -55,6 +79,13 @@ where
         enc.decrypt(&self.0)
     }
 
+    pub fn decrypt_authenticated(
+        &self,
+        enc: &AuthenticatedEncryption<G>,
+    ) -> FastCryptoResult<Vec<u8>> {
+        enc.decrypt(&self.0)
+    }
+
     pub fn create_recovery_package<R: AllowedRng>(
         &self,
         enc: &Encryption<G>,
@@ -100,6 +131,33 @@ where
         Encryption::<G>::deterministic_encrypt(msg, r_g, r_x_g, info)
     }
 
+    #[cfg(test)]
+    pub fn encrypt_authenticated<R: AllowedRng>(
+        &self,
+        msg: &[u8],
+        rng: &mut R,
+    ) -> AuthenticatedEncryption<G> {
+        AuthenticatedEncryption::<G>::encrypt(&self.0, msg, rng)
+    }
+
+    /// Deterministic counterpart of `encrypt_authenticated` for callers that already have their
+    /// own fresh `(r_g, r_x_g)` ephemeral DH pair, e.g. one produced and proven-correct elsewhere.
+    ///
+    /// Safety: the GCM nonce here is fixed to all-zero, so encrypting two different messages
+    /// under the same `(r_x_g, info)` pair reuses both the key and the nonce. Unlike the
+    /// CTR-based `deterministic_encrypt`, which only leaks the XOR of the two plaintexts on
+    /// reuse, GCM nonce reuse additionally lets an attacker forge valid tags for arbitrary
+    /// ciphertexts under that key (the "forbidden attack"). Callers MUST ensure `r_x_g` is fresh
+    /// (or `info` otherwise varies) for every message encrypted to a given recipient.
+    pub fn deterministic_encrypt_authenticated(
+        msg: &[u8],
+        r_g: &G,
+        r_x_g: &G,
+        info: usize,
+    ) -> AuthenticatedEncryption<G> {
+        AuthenticatedEncryption::<G>::deterministic_encrypt(msg, r_g, r_x_g, info)
+    }
+
     pub fn decrypt_with_recovery_package(
         &self,
         pkg: &RecoveryPackage<G>,
@@ -120,6 +178,71 @@ where
     }
 }
 
+impl<G> PublicKey<G>
+where
+    G: GroupElement + Serialize + DeserializeOwned,
+    <G as GroupElement>::ScalarType: FiatShamirChallenge + Zeroize + From<u64>,
+{
+    /// Threshold decryption from several custodians' recovery packages at once.
+    ///
+    /// Each entry pairs the party id of a custodian's share (the x-coordinate used for Lagrange
+    /// interpolation) with their public key and the `RecoveryPackage` they produced for `enc`.
+    /// All N `DdhTupleNizk` proofs are verified together via `DdhTupleNizk::verify_batch` - one
+    /// multi-scalar multiplication instead of N individual checks - and only then are the
+    /// verified partial decryption points Lagrange-combined at x = 0 into the full `rxG` and used
+    /// to recover the plaintext, instead of callers recovering one custodian at a time and
+    /// combining the points themselves.
+    pub fn decrypt_with_recovery_packages(
+        shares: &[(u64, PublicKey<G>, RecoveryPackage<G>)],
+        random_oracle: &RandomOracle,
+        enc: &Encryption<G>,
+    ) -> FastCryptoResult<Vec<u8>> {
+        if shares.is_empty() {
+            return Err(FastCryptoError::InvalidInput);
+        }
+        let proofs = shares
+            .iter()
+            .map(|(_, pk, pkg)| (pk.0, pkg.ephemeral_key, &pkg.proof))
+            .collect::<Vec<_>>();
+        DdhTupleNizk::verify_batch(&proofs, &enc.ephemeral_key, random_oracle)?;
+        let rxg = Self::lagrange_combine(shares)?;
+        Ok(enc.decrypt_from_partial_decryption(&rxg))
+    }
+
+    /// Combine already-verified partial decryption points `{(id_i, share_i)}` into
+    /// `rxG = Σ λ_i(0) · share_i`, the Lagrange interpolation of the points at x = 0.
+    fn lagrange_combine(shares: &[(u64, PublicKey<G>, RecoveryPackage<G>)]) -> FastCryptoResult<G> {
+        let mut ids: Vec<u64> = shares.iter().map(|(id, _, _)| *id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        if ids.len() != shares.len() {
+            // A repeated id would silently drop every copy of it from the "other" set used by
+            // `lagrange_coefficient` below, combining the wrong node set into a point that isn't
+            // the true Lagrange interpolation - with no algebraic way to detect the mistake after
+            // the fact, since `Encryption<G>`'s AES-CTR decryption has no integrity tag.
+            return Err(FastCryptoError::InvalidInput);
+        }
+        shares.iter().try_fold(G::zero(), |acc, (id, _, pkg)| {
+            let lambda = Self::lagrange_coefficient(*id, &ids)?;
+            Ok(acc + pkg.ephemeral_key * lambda)
+        })
+    }
+
+    /// `λ_i(0) = Π_{j≠i} id_j / (id_j - id_i)`, the Lagrange basis polynomial for party `id`
+    /// evaluated at x = 0, given the full set of party ids `ids`.
+    fn lagrange_coefficient(id: u64, ids: &[u64]) -> FastCryptoResult<G::ScalarType> {
+        let x_i = G::ScalarType::from(id);
+        ids.iter().filter(|&&other| other != id).try_fold(
+            G::ScalarType::from(1u64),
+            |acc, &other| {
+                let x_j = G::ScalarType::from(other);
+                let denominator = (x_j - x_i).inverse()?;
+                Ok(acc * x_j * denominator)
+            },
+        )
+    }
+}
+
 impl<G: GroupElement> From<G> for PublicKey<G> {
     fn from(p: G) -> Self {
         Self(p)
@@ -127,19 +250,33 @@ impl<G: GroupElement> From<G> for PublicKey<G> {
 }
 
 impl<G: GroupElement + Serialize> Encryption<G> {
-    fn sym_encrypt(k: &G, info: usize) -> Aes256Ctr {
+    fn sym_encrypt(k: &G, info: usize, ad: &[u8]) -> Aes256Ctr {
         Aes256Ctr::new(
-            AesKey::<U32>::from_bytes(&Self::hkdf(k, info))
+            AesKey::<U32>::from_bytes(&Self::hkdf(k, info, ad))
                 .expect("New shouldn't fail as use fixed size key is used"),
         )
     }
+
     fn deterministic_encrypt(msg: &[u8], r_g: &G, r_x_g: &G, hkdf_info: usize) -> Self {
-        let cipher = Self::sym_encrypt(r_x_g, hkdf_info);
+        Self::deterministic_encrypt_with_ad(msg, r_g, r_x_g, hkdf_info, &[])
+    }
+
+    /// Same as `deterministic_encrypt` but additionally binds `ad` into the derived key, so a
+    /// ciphertext produced for one `ad` cannot be decrypted as if it were produced for another.
+    fn deterministic_encrypt_with_ad(
+        msg: &[u8],
+        r_g: &G,
+        r_x_g: &G,
+        hkdf_info: usize,
+        ad: &[u8],
+    ) -> Self {
+        let cipher = Self::sym_encrypt(r_x_g, hkdf_info, ad);
         let data = cipher.encrypt(&Self::fixed_zero_nonce(), msg);
         Self {
             ephemeral_key: *r_g,
             data,
             hkdf_info,
+            ad: ad.to_vec(),
         }
     }
 
@@ -157,7 +294,7 @@ impl<G: GroupElement + Serialize> Encryption<G> {
     }
 
     pub fn decrypt_from_partial_decryption(&self, partial_key: &G) -> Vec<u8> {
-        let cipher = Self::sym_encrypt(partial_key, self.hkdf_info);
+        let cipher = Self::sym_encrypt(partial_key, self.hkdf_info, &self.ad);
         cipher
             .decrypt(&Self::fixed_zero_nonce(), &self.data)
             .expect("Decrypt should never fail for CTR mode")
@@ -167,9 +304,10 @@ impl<G: GroupElement + Serialize> Encryption<G> {
         &self.ephemeral_key
     }
 
-    fn hkdf(ikm: &G, info: usize) -> Vec<u8> {
+    fn hkdf(ikm: &G, info: usize, ad: &[u8]) -> Vec<u8> {
         let ikm = bcs::to_bytes(ikm).expect("serialize should never fail");
-        let info = info.to_be_bytes();
+        let mut info = info.to_be_bytes().to_vec();
+        info.extend_from_slice(ad);
         hkdf_sha3_256(
             &HkdfIkm::from_bytes(ikm.as_slice()).expect("hkdf_sha3_256 should work with any input"),
             &[],
@@ -185,37 +323,94 @@ impl<G: GroupElement + Serialize> Encryption<G> {
     }
 }
 
+impl<G: GroupElement + Serialize> AuthenticatedEncryption<G> {
+    fn sym_encrypt_aead(k: &G, info: usize) -> Aes256Gcm {
+        Aes256Gcm::new(
+            AesKey::<U32>::from_bytes(&Encryption::<G>::hkdf(k, info, &[]))
+                .expect("New shouldn't fail as use fixed size key is used"),
+        )
+    }
+
+    fn deterministic_encrypt(msg: &[u8], r_g: &G, r_x_g: &G, hkdf_info: usize) -> Self {
+        let cipher = Self::sym_encrypt_aead(r_x_g, hkdf_info);
+        let aad = bcs::to_bytes(r_g).expect("serialize should never fail");
+        let data = cipher.encrypt_authenticated(&Self::fixed_zero_nonce(), &aad, msg);
+        Self {
+            ephemeral_key: *r_g,
+            data,
+            hkdf_info,
+        }
+    }
+
+    #[cfg(test)]
+    fn encrypt<R: AllowedRng>(x_g: &G, msg: &[u8], rng: &mut R) -> Self {
+        let r = G::ScalarType::rand(rng);
+        let r_g = G::generator() * r;
+        let r_x_g = *x_g * r;
+        Self::deterministic_encrypt(msg, &r_g, &r_x_g, 0)
+    }
+
+    fn decrypt(&self, sk: &G::ScalarType) -> FastCryptoResult<Vec<u8>> {
+        let partial_key = self.ephemeral_key * sk;
+        self.decrypt_from_partial_decryption(&partial_key)
+    }
+
+    /// Decrypt using a partial decryption point, i.e., `sk * ephemeral_key` for some secret key
+    /// `sk`, instead of the secret key itself. Fails if the GCM tag does not verify, which also
+    /// catches a partial decryption point that does not match the key used to encrypt.
+    pub fn decrypt_from_partial_decryption(&self, partial_key: &G) -> FastCryptoResult<Vec<u8>> {
+        let cipher = Self::sym_encrypt_aead(partial_key, self.hkdf_info);
+        let aad = bcs::to_bytes(&self.ephemeral_key).expect("serialize should never fail");
+        cipher.decrypt_authenticated(&Self::fixed_zero_nonce(), &aad, &self.data)
+    }
+
+    pub fn ephemeral_key(&self) -> &G {
+        &self.ephemeral_key
+    }
+
+    fn fixed_zero_nonce() -> InitializationVector<U12> {
+        InitializationVector::<U12>::from_bytes(&[0u8; 12])
+            .expect("U12 could always be set from a 12 bytes array of zeros")
+    }
+}
+
 impl<G: GroupElement + Serialize> MultiRecipientEncryption<G>
 where
     <G as GroupElement>::ScalarType: FiatShamirChallenge,
 {
     pub fn encrypt<R: AllowedRng>(
-        pk_and_msgs: &[(PublicKey<G>, Vec<u8>)],
+        pk_and_msgs: &[(PublicKey<G>, Vec<u8>, Vec<u8>)],
         random_oracle: &RandomOracle,
         rng: &mut R,
     ) -> MultiRecipientEncryption<G> {
         let r = G::ScalarType::rand(rng);
         let r_g = G::generator() * r;
-        let encs = pk_and_msgs
+        let (encs, ads): (Vec<_>, Vec<_>) = pk_and_msgs
             .iter()
             .enumerate()
-            .map(|(info, (pk, msg))| {
+            .map(|(info, (pk, msg, ad))| {
                 let r_x_g = pk.0 * r;
-                Encryption::<G>::deterministic_encrypt(msg, &r_g, &r_x_g, info).data
+                let data =
+                    Encryption::<G>::deterministic_encrypt_with_ad(msg, &r_g, &r_x_g, info, ad)
+                        .data;
+                (data, ad.clone())
             })
-            .collect::<Vec<_>>();
-        // Bind the NIZK to the encrypted messages by adding them as inputs to the RO.
-        let encs_bytes = bcs::to_bytes(&encs).expect("serialize should never fail");
+            .unzip();
+        // Bind the NIZK to the encrypted messages and their associated data by adding them as
+        // inputs to the RO.
+        let encs_bytes = bcs::to_bytes(&(&encs, &ads)).expect("serialize should never fail");
         let nizk = DLNizk::<G>::create(&r, &r_g, &encs_bytes, random_oracle, rng);
-        Self(r_g, encs, nizk)
+        Self(r_g, encs, nizk, ads)
     }
 
     pub fn get_encryption(&self, i: usize) -> FastCryptoResult<Encryption<G>> {
         let buffer = self.1.get(i).ok_or(FastCryptoError::InvalidInput)?;
+        let ad = self.3.get(i).ok_or(FastCryptoError::InvalidInput)?;
         Ok(Encryption {
             ephemeral_key: self.0,
             data: buffer.clone(),
             hkdf_info: i,
+            ad: ad.clone(),
         })
     }
 
@@ -227,7 +422,7 @@ where
     }
 
     pub fn verify(&self, random_oracle: &RandomOracle) -> FastCryptoResult<()> {
-        let encs_bytes = bcs::to_bytes(&self.1).expect("serialize should never fail");
+        let encs_bytes = bcs::to_bytes(&(&self.1, &self.3)).expect("serialize should never fail");
         self.2.verify(&self.0, &encs_bytes, random_oracle)?;
         // Encryptions cannot be empty.
         self.1
@@ -254,3 +449,138 @@ where
         self.1[dst] = self.1[src].clone();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fastcrypto::groups::bls12381::G1Element;
+
+    type G = G1Element;
+
+    #[test]
+    fn authenticated_encryption_round_trips_and_detects_tampering() {
+        let mut rng = rand::thread_rng();
+        let sk = PrivateKey::<G>::new(&mut rng);
+        let pk = PublicKey::<G>::from_private_key(&sk);
+        let msg = b"super secret".to_vec();
+
+        let mut ct = pk.encrypt_authenticated(&msg, &mut rng);
+        assert_eq!(sk.decrypt_authenticated(&ct).unwrap(), msg);
+
+        // Unlike the CTR-based `Encryption<G>`, a flipped ciphertext byte must fail the GCM tag
+        // rather than silently decrypt to garbage.
+        ct.data[0] ^= 1;
+        assert!(sk.decrypt_authenticated(&ct).is_err());
+    }
+
+    #[test]
+    fn multi_recipient_encryption_binds_associated_data_to_slot() {
+        let mut rng = rand::thread_rng();
+        let sk0 = PrivateKey::<G>::new(&mut rng);
+        let pk0 = PublicKey::<G>::from_private_key(&sk0);
+        let sk1 = PrivateKey::<G>::new(&mut rng);
+        let pk1 = PublicKey::<G>::from_private_key(&sk1);
+        let ro = RandomOracle::new("test multi-recipient");
+
+        let enc = MultiRecipientEncryption::<G>::encrypt(
+            &[
+                (pk0.clone(), b"to 0".to_vec(), b"ad-0".to_vec()),
+                (pk1.clone(), b"to 1".to_vec(), b"ad-1".to_vec()),
+            ],
+            &ro,
+            &mut rng,
+        );
+        enc.verify(&ro).unwrap();
+        assert_eq!(sk0.decrypt(&enc.get_encryption(0).unwrap()), b"to 0");
+        assert_eq!(sk1.decrypt(&enc.get_encryption(1).unwrap()), b"to 1");
+
+        // `verify` recomputes the DLNizk's Fiat-Shamir challenge from `(self.1, self.3)`, so
+        // swapping two ciphertext slots changes the message the proof was bound to at `create`
+        // time and the bundle must be rejected outright, not just decrypt incorrectly.
+        let mut swapped = enc.clone();
+        swapped.swap_for_testing(0, 1);
+        assert!(swapped.verify(&ro).is_err());
+
+        // To isolate what the new `ad` field buys on its own - independent of the pre-existing
+        // `hkdf_info = i` binding, which would already break a same-slot swap - reconstruct slot
+        // 0's `Encryption` with slot 1's associated data spliced in, leaving the ciphertext bytes
+        // and `hkdf_info` untouched. The derived AES key depends on `ad`, so decrypting under the
+        // wrong one must not recover the original plaintext, even though nothing about the slot
+        // index changed.
+        let mut retargeted = enc.get_encryption(0).unwrap();
+        retargeted.ad = enc.get_encryption(1).unwrap().ad;
+        assert_ne!(sk0.decrypt(&retargeted), b"to 0");
+    }
+
+    /// Shamir-shares `secret` over a degree `threshold - 1` polynomial and returns each party's
+    /// `(id, private key share)`, `id`s starting at 1 since id 0 would make a share's private key
+    /// equal to the polynomial's free (secret) coefficient.
+    fn shamir_share(
+        secret: <G as GroupElement>::ScalarType,
+        threshold: usize,
+        num_parties: u64,
+        rng: &mut impl rand::RngCore,
+    ) -> Vec<(u64, PrivateKey<G>)> {
+        let mut coefficients = vec![secret];
+        for _ in 1..threshold {
+            coefficients.push(<G as GroupElement>::ScalarType::rand(rng));
+        }
+        (1..=num_parties)
+            .map(|id| {
+                let x = <G as GroupElement>::ScalarType::from(id);
+                let mut y = <G as GroupElement>::ScalarType::zero();
+                let mut x_pow = <G as GroupElement>::ScalarType::from(1u64);
+                for c in &coefficients {
+                    y = y + *c * x_pow;
+                    x_pow = x_pow * x;
+                }
+                (id, PrivateKey::from(y))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn decrypt_with_recovery_packages_recovers_threshold_encrypted_message() {
+        let mut rng = rand::thread_rng();
+        let secret = <G as GroupElement>::ScalarType::rand(&mut rng);
+        let pk = PublicKey::<G>::from_private_key(&PrivateKey::from(secret));
+        let shares = shamir_share(secret, 2, 3, &mut rng);
+        let ro = RandomOracle::new("test threshold recovery");
+
+        let msg = b"threshold secret".to_vec();
+        let enc = pk.encrypt(&msg, &mut rng);
+
+        let recovery_shares: Vec<_> = shares
+            .iter()
+            .take(2)
+            .map(|(id, sk)| {
+                let share_pk = PublicKey::<G>::from_private_key(sk);
+                let pkg = sk.create_recovery_package(&enc, &ro, &mut rng);
+                (*id, share_pk, pkg)
+            })
+            .collect();
+
+        let recovered =
+            PublicKey::decrypt_with_recovery_packages(&recovery_shares, &ro, &enc).unwrap();
+        assert_eq!(recovered, msg);
+    }
+
+    #[test]
+    fn decrypt_with_recovery_packages_rejects_duplicate_ids() {
+        let mut rng = rand::thread_rng();
+        let secret = <G as GroupElement>::ScalarType::rand(&mut rng);
+        let pk = PublicKey::<G>::from_private_key(&PrivateKey::from(secret));
+        let shares = shamir_share(secret, 2, 3, &mut rng);
+        let ro = RandomOracle::new("test threshold recovery duplicate ids");
+
+        let msg = b"threshold secret".to_vec();
+        let enc = pk.encrypt(&msg, &mut rng);
+
+        let (id, sk) = &shares[0];
+        let share_pk = PublicKey::<G>::from_private_key(sk);
+        let pkg = sk.create_recovery_package(&enc, &ro, &mut rng);
+        let duplicated_shares = vec![(*id, share_pk.clone(), pkg.clone()), (*id, share_pk, pkg)];
+
+        assert!(PublicKey::decrypt_with_recovery_packages(&duplicated_shares, &ro, &enc).is_err());
+    }
+}